@@ -0,0 +1,157 @@
+//! Higher-level duplicate-finding subsystem built on top of the block-averaging and
+//! chi-square comparison primitives in the crate root.
+
+use crate::{average_gb_blocks, compare_images_chisquare};
+use image::GenericImageView;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+struct DisjointSet {
+    parent: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(n: usize) -> Self {
+        Self { parent: (0..n).collect() }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        if self.parent[x] != x {
+            self.parent[x] = self.find(self.parent[x]);
+        }
+        self.parent[x]
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        if root_a != root_b {
+            self.parent[root_a] = root_b;
+        }
+    }
+}
+
+// Coarse aspect-ratio bucket, reusing the integer width/height comparison from
+// `aspectratio_comparison`: images whose width / height ratio truncates to the same integer
+// are considered compatible.
+fn aspect_ratio_bucket(width: u32, height: u32) -> u32 {
+    width / height.max(1)
+}
+
+// Coarse mean-color bucket so the full chi-square comparison only runs between images of
+// roughly the same average color.
+fn mean_color_bucket(mean: [u8; 3]) -> (u8, u8, u8) {
+    (mean[0] / 32, mean[1] / 32, mean[2] / 32)
+}
+
+// Unweighted average of an image's own block averages, so the mean-color bucket doesn't
+// require re-scanning the image a second time.
+fn mean_of_blocks(blocks: &[[u8; 3]]) -> [u8; 3] {
+    let len = blocks.len().max(1) as u64;
+    let mut sum = [0u64; 3];
+    for block in blocks {
+        for i in 0..3 {
+            sum[i] += block[i] as u64;
+        }
+    }
+    [(sum[0] / len) as u8, (sum[1] / len) as u8, (sum[2] / len) as u8]
+}
+
+struct IndexedImage {
+    path: PathBuf,
+    blocks: Vec<[u8; 3]>,
+    pixel_count: u64,
+    bucket: (u32, (u8, u8, u8)),
+}
+
+// Groups images under `threshold` chi-square error into clusters. Candidates are bucketed by
+// aspect ratio and mean color first, and only compared within a bucket, so this stays well
+// short of all-pairs. Each group is sorted largest-first.
+//
+// Reads paths with `image::open`, so it's local-files only; download URLs with
+// `crate::download_image` and save them first if that's where the images live.
+pub fn find_duplicate_groups(paths: &[PathBuf], x_segments: usize, y_segments: usize, threshold: f64) -> Vec<Vec<PathBuf>> {
+    let images: Vec<IndexedImage> = paths
+        .iter()
+        .filter_map(|path| {
+            let image = image::open(path).ok()?;
+            let (width, height) = image.dimensions();
+            let blocks = average_gb_blocks(&image, x_segments, y_segments);
+            let bucket = (aspect_ratio_bucket(width, height), mean_color_bucket(mean_of_blocks(&blocks)));
+            Some(IndexedImage {
+                path: path.clone(),
+                blocks,
+                pixel_count: width as u64 * height as u64,
+                bucket,
+            })
+        })
+        .collect();
+
+    // Group indices by bucket first, so the chi-square comparison below only ever runs
+    // between images that already share an aspect ratio and mean color, instead of scanning
+    // every pair in the whole set.
+    let mut buckets: HashMap<(u32, (u8, u8, u8)), Vec<usize>> = HashMap::new();
+    for (i, image) in images.iter().enumerate() {
+        buckets.entry(image.bucket).or_default().push(i);
+    }
+
+    let mut dsu = DisjointSet::new(images.len());
+    for indices in buckets.values() {
+        for (pos, &i) in indices.iter().enumerate() {
+            for &j in &indices[pos + 1..] {
+                let error = compare_images_chisquare(&images[i].blocks, &images[j].blocks);
+                if error < threshold {
+                    dsu.union(i, j);
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..images.len() {
+        let root = dsu.find(i);
+        groups.entry(root).or_default().push(i);
+    }
+
+    groups
+        .into_values()
+        .map(|mut indices| {
+            // Highest resolution (the inverse of `smallest_dimensions`) goes first.
+            indices.sort_by(|&a, &b| images[b].pixel_count.cmp(&images[a].pixel_count));
+            indices.into_iter().map(|i| images[i].path.clone()).collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn write_solid_image(name: &str, width: u32, height: u32, color: [u8; 3]) -> PathBuf {
+        let image = RgbImage::from_fn(width, height, |_, _| Rgb(color));
+        let path = std::env::temp_dir().join(name);
+        image.save(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn find_duplicate_groups_clusters_near_duplicates_and_keeps_distinct_images_apart() {
+        let red_small = write_solid_image("duplicates_test_red_small.png", 20, 20, [200, 20, 20]);
+        let red_large = write_solid_image("duplicates_test_red_large.png", 40, 40, [205, 18, 22]);
+        let blue = write_solid_image("duplicates_test_blue.png", 20, 20, [20, 20, 200]);
+
+        let paths = vec![red_small.clone(), red_large.clone(), blue.clone()];
+        let groups = find_duplicate_groups(&paths, 4, 4, 50.0);
+
+        assert_eq!(groups.len(), 2);
+        let red_group = groups.iter().find(|g| g.contains(&red_small)).unwrap();
+        assert_eq!(red_group.len(), 2);
+        // Highest-resolution member (the large one) sorts first.
+        assert_eq!(red_group[0], red_large);
+
+        for path in [red_small, red_large, blue] {
+            std::fs::remove_file(path).ok();
+        }
+    }
+}
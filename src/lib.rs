@@ -1,15 +1,59 @@
 use glam::DVec4;
 use itertools::{Chunks, Itertools};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use reqwest::get;
 use image::{DynamicImage, GenericImageView, ImageReader, Rgba};
 use std::io::Cursor;
 
+pub mod duplicates;
+
 pub async fn download_image(url: &str) -> Result<image::DynamicImage, Box<dyn std::error::Error>> {
     let response = get(url).await?.bytes().await?;
     let img = ImageReader::new(Cursor::new(response)).with_guessed_format()?.decode()?;
     Ok(img)
 }
 
+// Computes the average [r, g, b] of the block at grid position (x, y), handling the
+// remainder for the last row/column the same way the serial and parallel paths do.
+fn average_block(image: &DynamicImage, x: usize, y: usize, x_segments: usize, y_segments: usize, block_width: u32, block_height: u32) -> [u8; 3] {
+    let (img_width, img_height) = image.dimensions();
+    let mut sum_r = 0u64;
+    let mut sum_g = 0u64;
+    let mut sum_b = 0u64;
+    let mut pixel_count = 0u64;
+
+    // Determine the size of each block, handling the remainder for the last blocks
+    let current_block_width = if x == x_segments - 1 {
+        img_width - (block_width * (x_segments as u32 - 1))
+    } else {
+        block_width
+    };
+    let current_block_height = if y == y_segments - 1 {
+        img_height - (block_height * (y_segments as u32 - 1))
+    } else {
+        block_height
+    };
+
+    for i in 0..current_block_width {
+        for j in 0..current_block_height {
+            let pixel = image.get_pixel(x as u32 * block_width + i, y as u32 * block_height + j);
+            let Rgba([r, g, b, _]) = pixel;
+            sum_r += r as u64;
+            sum_g += g as u64;
+            sum_b += b as u64;
+            pixel_count += 1;
+        }
+    }
+
+    [
+        (sum_r / pixel_count) as u8,
+        (sum_g / pixel_count) as u8,
+        (sum_b / pixel_count) as u8,
+    ]
+}
+
+#[cfg(not(feature = "rayon"))]
 pub fn average_gb_blocks(image: &DynamicImage, x_segments: usize, y_segments: usize) -> Vec<[u8; 3]> {
     let (img_width, img_height) = image.dimensions();
     let block_width = img_width / x_segments as u32;
@@ -19,45 +63,42 @@ pub fn average_gb_blocks(image: &DynamicImage, x_segments: usize, y_segments: us
     // Iterate over the blocks
     for y in 0..y_segments {
         for x in 0..x_segments {
-            let mut sum_r = 0u64;
-            let mut sum_g = 0u64;
-            let mut sum_b = 0u64;
-            let mut pixel_count = 0u64;
-
-            // Determine the size of each block, handling the remainder for the last blocks
-            let current_block_width = if x == x_segments - 1 {
-                img_width - (block_width * (x_segments as u32 - 1))
-            } else {
-                block_width
-            };
-            let current_block_height = if y == y_segments - 1 {
-                img_height - (block_height * (y_segments as u32 - 1))
-            } else {
-                block_height
-            };
-
-            for i in 0..current_block_width {
-                for j in 0..current_block_height {
-                    let pixel = image.get_pixel(x as u32 * block_width + i, y as u32 * block_height + j);
-                    let Rgba([r, g, b, _]) = pixel;
-                    sum_r += r as u64;
-                    sum_g += g as u64;
-                    sum_b += b as u64;
-                    pixel_count += 1;
-                }
-            }
-
-            // Compute average for the block
-            block_averages.push([
-                (sum_r / pixel_count) as u8,
-                (sum_g / pixel_count) as u8,
-                (sum_b / pixel_count) as u8,
-            ]);
+            block_averages.push(average_block(image, x, y, x_segments, y_segments, block_width, block_height));
         }
     }
     block_averages
 }
 
+#[cfg(feature = "rayon")]
+pub fn average_gb_blocks(image: &DynamicImage, x_segments: usize, y_segments: usize) -> Vec<[u8; 3]> {
+    let (img_width, img_height) = image.dimensions();
+    let block_width = img_width / x_segments as u32;
+    let block_height = img_height / y_segments as u32;
+
+    (0..y_segments * x_segments)
+        .into_par_iter()
+        .map(|idx| {
+            let x = idx % x_segments;
+            let y = idx / x_segments;
+            average_block(image, x, y, x_segments, y_segments, block_width, block_height)
+        })
+        .collect()
+}
+
+/// Scores `query` against every entry in `library` in parallel and returns `(index, error)`
+/// pairs sorted by similarity (lowest chi-square error first). Requires the `rayon` feature.
+#[cfg(feature = "rayon")]
+pub fn compare_one_to_many(query: &Vec<[u8; 3]>, library: &[Vec<[u8; 3]>]) -> Vec<(usize, f64)> {
+    let mut scores: Vec<(usize, f64)> = library
+        .par_iter()
+        .enumerate()
+        .map(|(index, blocks)| (index, compare_images_chisquare(query, blocks)))
+        .collect();
+
+    scores.sort_by(|a, b| a.1.total_cmp(&b.1));
+    scores
+}
+
 // Function to compare two block-averaged images using Chi-square
 pub fn compare_images_chisquare(img1: &Vec<[u8; 3]>, img2: &Vec<[u8; 3]>) -> f64 {
     let mut chi_square = 0.0;
@@ -110,6 +151,384 @@ pub fn compare_images_chisquare_glam(img1: &Vec<[u8; 3]>, img2: &Vec<[u8; 3]>) -
     chi_square.element_sum()/total_count as f64
 }
 
+// Lanczos approximation of ln(Gamma(x)), g=7, n=9.
+fn ln_gamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const COEFFS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_312e-7,
+    ];
+
+    if x < 0.5 {
+        // Reflection formula
+        (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1.0 - x)
+    } else {
+        let x = x - 1.0;
+        let mut a = COEFFS[0];
+        let t = x + G + 0.5;
+        for (i, coeff) in COEFFS.iter().enumerate().skip(1) {
+            a += coeff / (x + i as f64);
+        }
+        0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + a.ln()
+    }
+}
+
+// Regularized lower incomplete gamma function P(a, x).
+fn regularized_lower_incomplete_gamma(a: f64, x: f64) -> f64 {
+    if x < 0.0 || a <= 0.0 {
+        return 0.0;
+    }
+    if x == 0.0 {
+        return 0.0;
+    }
+
+    if x < a + 1.0 {
+        // Series expansion: P(a,x) = e^-x x^a / Gamma(a+1) * sum_n x^n / (a+1)(a+2)...(a+n)
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        let mut n = a;
+        for _ in 0..200 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        sum * (-x + a * x.ln() - ln_gamma(a)).exp()
+    } else {
+        // Continued fraction for Q(a,x), then P = 1 - Q
+        let mut b = x + 1.0 - a;
+        let mut c = 1e300;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..200 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < 1e-300 {
+                d = 1e-300;
+            }
+            c = b + an / c;
+            if c.abs() < 1e-300 {
+                c = 1e-300;
+            }
+            d = 1.0 / d;
+            let del = d * c;
+            h *= del;
+            if (del - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        let q = (-x + a * x.ln() - ln_gamma(a)).exp() * h;
+        1.0 - q
+    }
+}
+
+// Like compare_images_chisquare, but normalizes by expected+1 and maps the statistic through
+// the upper-tail chi-square CDF (k = blocks*3 - 1 degrees of freedom), so the result is near
+// 1.0 for matching images and falls toward 0 as they diverge.
+pub fn chisquare_pvalue(img1: &Vec<[u8; 3]>, img2: &Vec<[u8; 3]>) -> f64 {
+    let mut chi_square = 0.0;
+
+    for (block1, block2) in img1.iter().zip(img2.iter()) {
+        for i in 0..3 {
+            let expected = block1[i] as f64;
+            let observed = block2[i] as f64;
+            chi_square += (observed - expected).powi(2) / (expected + 1.0);
+        }
+    }
+
+    let k = (img1.len().min(img2.len()) * 3).saturating_sub(1).max(1) as f64;
+    1.0 - regularized_lower_incomplete_gamma(k / 2.0, chi_square / 2.0)
+}
+
+/// One of the 8 dihedral transforms that can map one block grid onto another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    Identity,
+    FlipHorizontal,
+    FlipVertical,
+    Rotate180,
+    Transpose,
+    AntiTranspose,
+    Rotate90Cw,
+    Rotate90Ccw,
+}
+
+const ALL_TRANSFORMS: [Transform; 8] = [
+    Transform::Identity,
+    Transform::FlipHorizontal,
+    Transform::FlipVertical,
+    Transform::Rotate180,
+    Transform::Transpose,
+    Transform::AntiTranspose,
+    Transform::Rotate90Cw,
+    Transform::Rotate90Ccw,
+];
+
+// Transpose, AntiTranspose, Rotate90Cw and Rotate90Ccw swap the grid's width and height, so
+// they only produce a valid x_segments * y_segments grid (comparable block-for-block against
+// img1_blocks) when the grid is square.
+fn transform_swaps_axes(transform: Transform) -> bool {
+    matches!(
+        transform,
+        Transform::Transpose | Transform::AntiTranspose | Transform::Rotate90Cw | Transform::Rotate90Ccw
+    )
+}
+
+// Maps a destination (x, y) coordinate in the x_segments * y_segments grid back to the
+// source coordinate it reads from under `transform`.
+fn transform_source_coords(transform: Transform, x: usize, y: usize, x_segments: usize, y_segments: usize) -> (usize, usize) {
+    match transform {
+        Transform::Identity => (x, y),
+        Transform::FlipHorizontal => (x_segments - 1 - x, y),
+        Transform::FlipVertical => (x, y_segments - 1 - y),
+        Transform::Rotate180 => (x_segments - 1 - x, y_segments - 1 - y),
+        Transform::Transpose => (y, x),
+        Transform::AntiTranspose => (y_segments - 1 - y, x_segments - 1 - x),
+        Transform::Rotate90Cw => (y, x_segments - 1 - x),
+        Transform::Rotate90Ccw => (y_segments - 1 - y, x),
+    }
+}
+
+// Compares `img1_blocks` against `img2_blocks` re-indexed under `transform`, treating both
+// as an x_segments * y_segments grid, bailing out early once the accumulated error exceeds
+// `best_so_far`.
+fn chisquare_error_transformed(
+    img1_blocks: &[[u8; 3]],
+    img2_blocks: &[[u8; 3]],
+    x_segments: usize,
+    y_segments: usize,
+    transform: Transform,
+    best_so_far: f64,
+) -> f64 {
+    let mut chi_square = 0.0;
+
+    for y in 0..y_segments {
+        for x in 0..x_segments {
+            let (sx, sy) = transform_source_coords(transform, x, y, x_segments, y_segments);
+            let block1 = img1_blocks[y * x_segments + x];
+            let block2 = img2_blocks[sy * x_segments + sx];
+            for i in 0..3 {
+                let expected = block1[i] as f64;
+                let observed = block2[i] as f64;
+                chi_square += (observed - expected).powi(2);
+            }
+            if chi_square >= best_so_far {
+                return chi_square;
+            }
+        }
+    }
+
+    chi_square
+}
+
+/// Tries all 8 dihedral transforms of `img2_blocks` against `img1_blocks` and returns the
+/// transform (and its chi-square error) that aligns them best. Handles duplicates that were
+/// re-saved mirrored or rotated, which a fixed scan-order comparison would miss entirely.
+///
+/// The 4 transforms that swap width and height (`Transpose`, `AntiTranspose`, `Rotate90Cw`,
+/// `Rotate90Ccw`) only produce a grid with the same shape when `x_segments == y_segments`; for
+/// a non-square grid they're skipped rather than indexed out of bounds. The third element of
+/// the result is `false` in that case, signaling that a rotation/transpose duplicate could
+/// exist but wasn't searched for.
+pub fn compare_images_transformed(
+    img1_blocks: &Vec<[u8; 3]>,
+    img2_blocks: &Vec<[u8; 3]>,
+    x_segments: usize,
+    y_segments: usize,
+) -> (Transform, f64, bool) {
+    let mut best_transform = Transform::Identity;
+    let mut best_error = f64::MAX;
+    let exhaustive = x_segments == y_segments;
+
+    for &transform in ALL_TRANSFORMS.iter() {
+        if !exhaustive && transform_swaps_axes(transform) {
+            continue;
+        }
+        let error = chisquare_error_transformed(img1_blocks, img2_blocks, x_segments, y_segments, transform, best_error);
+        if error < best_error {
+            best_error = error;
+            best_transform = transform;
+        }
+    }
+
+    (best_transform, best_error, exhaustive)
+}
+
+const BASE83_ALPHABET: &[u8; 83] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+fn encode_base83(value: u64, length: usize) -> String {
+    let mut result = String::with_capacity(length);
+    for digit in (0..length).rev() {
+        let divisor = 83u64.pow(digit as u32);
+        result.push(BASE83_ALPHABET[((value / divisor) % 83) as usize] as char);
+    }
+    result
+}
+
+fn decode_base83(s: &str) -> u64 {
+    s.bytes().fold(0u64, |value, byte| {
+        let digit = BASE83_ALPHABET.iter().position(|&c| c == byte).unwrap_or(0);
+        value * 83 + digit as u64
+    })
+}
+
+fn signed_pow(value: f64, exponent: f64) -> f64 {
+    value.signum() * value.abs().powf(exponent)
+}
+
+fn srgb_to_linear(c: u8) -> f64 {
+    let c = c as f64;
+    if c > 10.31 {
+        ((c / 255.0 + 0.055) / 1.055).powf(2.4)
+    } else {
+        c / 255.0 / 12.92
+    }
+}
+
+fn linear_to_srgb(value: f64) -> u8 {
+    let v = value.clamp(0.0, 1.0);
+    let srgb = if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    (srgb * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+// Accumulates factor = normalization * sum_{x,y} color(x,y) * cos(pi*i*x/width) * cos(pi*j*y/height)
+// for the (i, j) DCT basis, in linear RGB.
+fn dct_basis_factor(image: &DynamicImage, i: u32, j: u32, width: u32, height: u32) -> [f64; 3] {
+    let normalization = if i == 0 && j == 0 { 1.0 } else { 2.0 };
+    let mut sum = [0.0f64; 3];
+
+    for y in 0..height {
+        for x in 0..width {
+            let Rgba([r, g, b, _]) = image.get_pixel(x, y);
+            let basis = (std::f64::consts::PI * i as f64 * x as f64 / width as f64).cos()
+                * (std::f64::consts::PI * j as f64 * y as f64 / height as f64).cos();
+            sum[0] += basis * srgb_to_linear(r);
+            sum[1] += basis * srgb_to_linear(g);
+            sum[2] += basis * srgb_to_linear(b);
+        }
+    }
+
+    let scale = normalization / (width as f64 * height as f64);
+    [sum[0] * scale, sum[1] * scale, sum[2] * scale]
+}
+
+/// Encodes a compact perceptual signature of `image` using `nx * ny` DCT components
+/// (BlurHash-style), as a base-83 string. The first component is the DC (average color);
+/// the rest are AC components that capture coarse structure. Cheap to store and compare at
+/// a few bytes per image, unlike a full `Vec<[u8; 3]>` block grid.
+pub fn image_signature(image: &DynamicImage, nx: usize, ny: usize) -> String {
+    let (width, height) = image.dimensions();
+    let mut factors = Vec::with_capacity(nx * ny);
+    for j in 0..ny {
+        for i in 0..nx {
+            factors.push(dct_basis_factor(image, i as u32, j as u32, width, height));
+        }
+    }
+
+    let dc = factors[0];
+    let ac = &factors[1..];
+
+    let max_ac = ac
+        .iter()
+        .flat_map(|c| c.iter().copied())
+        .fold(0.0f64, |max, v| max.max(v.abs()));
+
+    let quantised_max_ac = if ac.is_empty() {
+        0
+    } else {
+        ((max_ac * 166.0 - 0.5).floor() as i64).clamp(0, 82) as u64
+    };
+    let actual_max_ac = (quantised_max_ac as f64 + 1.0) / 166.0;
+
+    let size_flag = (nx - 1) + (ny - 1) * 9;
+    let mut result = encode_base83(size_flag as u64, 1);
+    result += &encode_base83(quantised_max_ac, 1);
+
+    let dc_int = (linear_to_srgb(dc[0]) as u64) << 16
+        | (linear_to_srgb(dc[1]) as u64) << 8
+        | linear_to_srgb(dc[2]) as u64;
+    result += &encode_base83(dc_int, 4);
+
+    for component in ac {
+        let quantised: Vec<u64> = component
+            .iter()
+            .map(|&v| {
+                (signed_pow(v / actual_max_ac, 0.5) * 9.0 + 9.5).floor().clamp(0.0, 18.0) as u64
+            })
+            .collect();
+        let combined = (quantised[0] * 19 + quantised[1]) * 19 + quantised[2];
+        result += &encode_base83(combined, 2);
+    }
+
+    result
+}
+
+// Decodes an `image_signature` string back into (nx, ny, components), each component the
+// same [r, g, b] factor space the encoder worked in.
+fn decode_signature(signature: &str) -> (usize, usize, Vec<[f64; 3]>) {
+    let bytes = signature.as_bytes();
+    let size_flag = decode_base83(std::str::from_utf8(&bytes[0..1]).unwrap());
+    let nx = (size_flag % 9) as usize + 1;
+    let ny = (size_flag / 9) as usize + 1;
+
+    let quantised_max_ac = decode_base83(std::str::from_utf8(&bytes[1..2]).unwrap());
+    let max_ac = (quantised_max_ac as f64 + 1.0) / 166.0;
+
+    let dc_int = decode_base83(std::str::from_utf8(&bytes[2..6]).unwrap());
+    let dc = [
+        srgb_to_linear(((dc_int >> 16) & 0xff) as u8),
+        srgb_to_linear(((dc_int >> 8) & 0xff) as u8),
+        srgb_to_linear((dc_int & 0xff) as u8),
+    ];
+
+    let mut components = vec![dc];
+    let mut offset = 6;
+    while offset + 2 <= bytes.len() {
+        let combined = decode_base83(std::str::from_utf8(&bytes[offset..offset + 2]).unwrap());
+        let qb = combined % 19;
+        let qg = (combined / 19) % 19;
+        let qr = combined / 19 / 19;
+        let decode_component = |q: u64| signed_pow((q as f64 - 9.0) / 9.0, 2.0) * max_ac;
+        components.push([decode_component(qr), decode_component(qg), decode_component(qb)]);
+        offset += 2;
+    }
+
+    (nx, ny, components)
+}
+
+/// Compares two `image_signature` strings by decoding both and summing squared per-component
+/// differences, so similarity can be judged from a few bytes per image instead of a full
+/// block grid.
+pub fn signature_distance(a: &str, b: &str) -> f64 {
+    let (_, _, components_a) = decode_signature(a);
+    let (_, _, components_b) = decode_signature(b);
+
+    components_a
+        .iter()
+        .zip(components_b.iter())
+        .map(|(ca, cb)| {
+            ca.iter()
+                .zip(cb.iter())
+                .map(|(x, y)| (x - y).powi(2))
+                .sum::<f64>()
+        })
+        .sum()
+}
+
 fn save_image(image: &DynamicImage, path: &str) -> Result<(), Box<dyn std::error::Error>> {
     image.save(path)?;
     Ok(())
@@ -125,6 +544,54 @@ pub fn smallest_dimensions(img1: &image::DynamicImage, img2: &image::DynamicImag
     }
 }
 
+// Pixel count of the block at grid position (x, y), matching the remainder handling in
+// `average_gb_blocks` so a block's weight reflects how many pixels actually fed its average.
+fn block_pixel_count(x: usize, y: usize, x_segments: usize, y_segments: usize, width: u32, height: u32) -> u64 {
+    let block_width = width / x_segments as u32;
+    let block_height = height / y_segments as u32;
+    let current_block_width = if x == x_segments - 1 {
+        width - (block_width * (x_segments as u32 - 1))
+    } else {
+        block_width
+    };
+    let current_block_height = if y == y_segments - 1 {
+        height - (block_height * (y_segments as u32 - 1))
+    } else {
+        block_height
+    };
+    current_block_width as u64 * current_block_height as u64
+}
+
+// Like compare_images_chisquare, but resizes both images to a common resolution (the smaller
+// of the two, via smallest_dimensions) before block averaging, and weights each block by its
+// true pixel count instead of assuming uniform blocks.
+pub fn compare_images_normalized(img1: &DynamicImage, img2: &DynamicImage, x_segments: usize, y_segments: usize) -> f64 {
+    let (_, width, height) = smallest_dimensions(img1, img2);
+    let resized1 = img1.resize_exact(width, height, image::imageops::FilterType::Triangle);
+    let resized2 = img2.resize_exact(width, height, image::imageops::FilterType::Triangle);
+
+    let blocks1 = average_gb_blocks(&resized1, x_segments, y_segments);
+    let blocks2 = average_gb_blocks(&resized2, x_segments, y_segments);
+
+    let mut chi_square = 0.0;
+    let mut total_weight = 0.0;
+
+    for y in 0..y_segments {
+        for x in 0..x_segments {
+            let idx = y * x_segments + x;
+            let weight = block_pixel_count(x, y, x_segments, y_segments, width, height) as f64;
+            for i in 0..3 {
+                let expected = blocks1[idx][i] as f64;
+                let observed = blocks2[idx][i] as f64;
+                chi_square += weight * (observed - expected).powi(2);
+            }
+            total_weight += weight * 3.0;
+        }
+    }
+
+    chi_square / total_weight
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,4 +645,136 @@ mod tests {
         let result_std = compare_images_chisquare(&blocks1, &blocks2);
         let result_simd = compare_images_chisquare_glam(&blocks1, &blocks2);
         assert_eq!(result_simd, result_std);
-    }}
\ No newline at end of file
+    }
+
+    #[test]
+    fn chisquare_pvalue_identical_images_is_near_one() {
+        let blocks = vec![[10u8, 20u8, 30u8]; 100];
+        let result = chisquare_pvalue(&blocks, &blocks);
+        assert!(result > 1.0 - 1e-6);
+    }
+
+    #[tokio::test]
+    async fn chisquare_pvalue_dissimilar_images_is_low() {
+        let img1 = download_image(URLSOMEWHATSIMILAR).await.unwrap();
+        let img2 = download_image(URLSOMEWHATSIMILAR2).await.unwrap();
+        let blocks1 = average_gb_blocks(&img1, 10, 10);
+        let blocks2 = average_gb_blocks(&img2, 10, 10);
+        let result = chisquare_pvalue(&blocks1, &blocks2);
+        assert!(result < 0.5);
+    }
+
+    #[test]
+    fn compare_images_transformed_detects_horizontal_flip() {
+        // 2x2 grid, distinct colors in every cell so each transform is unambiguous.
+        let original = vec![[0, 0, 0], [10, 10, 10], [20, 20, 20], [30, 30, 30]];
+        let flipped = vec![[10, 10, 10], [0, 0, 0], [30, 30, 30], [20, 20, 20]];
+
+        let (transform, error, exhaustive) = compare_images_transformed(&original, &flipped, 2, 2);
+        assert_eq!(transform, Transform::FlipHorizontal);
+        assert_eq!(error, 0.0);
+        assert!(exhaustive);
+    }
+
+    #[test]
+    fn compare_images_transformed_identity_for_same_grid() {
+        let blocks = vec![[5, 5, 5], [15, 15, 15], [25, 25, 25], [35, 35, 35]];
+        let (transform, error, exhaustive) = compare_images_transformed(&blocks, &blocks, 2, 2);
+        assert_eq!(transform, Transform::Identity);
+        assert_eq!(error, 0.0);
+        assert!(exhaustive);
+    }
+
+    #[test]
+    fn compare_images_transformed_detects_transpose() {
+        // original[(x,y)] = original[y*2+x]: A, B, C, D at (0,0) (1,0) (0,1) (1,1).
+        let original = vec![[0, 0, 0], [10, 10, 10], [20, 20, 20], [30, 30, 30]];
+        // Swap the off-diagonal cells (B <-> C), which is exactly a matrix transpose.
+        let transposed = vec![[0, 0, 0], [20, 20, 20], [10, 10, 10], [30, 30, 30]];
+
+        let (transform, error, exhaustive) = compare_images_transformed(&original, &transposed, 2, 2);
+        assert_eq!(transform, Transform::Transpose);
+        assert_eq!(error, 0.0);
+        assert!(exhaustive);
+    }
+
+    #[test]
+    fn compare_images_transformed_detects_rotate90cw() {
+        let original = vec![[0, 0, 0], [10, 10, 10], [20, 20, 20], [30, 30, 30]];
+        let rotated = vec![[10, 10, 10], [30, 30, 30], [0, 0, 0], [20, 20, 20]];
+
+        let (transform, error, exhaustive) = compare_images_transformed(&original, &rotated, 2, 2);
+        assert_eq!(transform, Transform::Rotate90Cw);
+        assert_eq!(error, 0.0);
+        assert!(exhaustive);
+    }
+
+    #[test]
+    fn compare_images_transformed_does_not_panic_on_non_square_grid() {
+        // A 3x2 grid compared against itself used to index out of bounds in the
+        // axis-swapping transforms (Transpose/AntiTranspose/Rotate90Cw/Rotate90Ccw), which
+        // only make sense for a square grid.
+        let blocks = vec![[0, 0, 0], [10, 10, 10], [20, 20, 20], [30, 30, 30], [40, 40, 40], [50, 50, 50]];
+        let (transform, error, exhaustive) = compare_images_transformed(&blocks, &blocks, 3, 2);
+        assert_eq!(transform, Transform::Identity);
+        assert_eq!(error, 0.0);
+        assert!(!exhaustive);
+    }
+
+    #[tokio::test]
+    async fn image_signature_is_stable_and_self_similar() {
+        let img = download_image(URLSMALL).await.unwrap();
+        let sig1 = image_signature(&img, 4, 3);
+        let sig2 = image_signature(&img, 4, 3);
+        assert_eq!(sig1, sig2);
+        assert_eq!(signature_distance(&sig1, &sig2), 0.0);
+    }
+
+    #[tokio::test]
+    async fn image_signature_distance_is_higher_for_dissimilar_images() {
+        let img1 = download_image(URLSMALL).await.unwrap();
+        let img2 = download_image(URLOTHER).await.unwrap();
+        let same_sig = image_signature(&img1, 4, 3);
+        let other_sig = image_signature(&img2, 4, 3);
+        assert!(signature_distance(&same_sig, &other_sig) > 0.0);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[tokio::test]
+    async fn compare_one_to_many_ranks_the_matching_image_first() {
+        let query_img = download_image(URLSMALL).await.unwrap();
+        let other_img = download_image(URLOTHER).await.unwrap();
+        let query = average_gb_blocks(&query_img, 10, 10);
+        let library = vec![average_gb_blocks(&other_img, 10, 10), query.clone()];
+
+        let scores = compare_one_to_many(&query, &library);
+        assert_eq!(scores[0].0, 1);
+        assert_eq!(scores[0].1, 0.0);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn compare_one_to_many_does_not_panic_on_nan_score() {
+        // An empty block vector makes compare_images_chisquare divide 0.0/0.0, producing NaN.
+        let query: Vec<[u8; 3]> = vec![];
+        let library: Vec<Vec<[u8; 3]>> = vec![vec![], vec![[1, 2, 3]]];
+
+        let scores = compare_one_to_many(&query, &library);
+        assert_eq!(scores.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn compare_images_normalized_handles_mismatched_resolutions() {
+        let img1 = download_image(URLSMALL).await.unwrap();
+        let img2 = download_image(URLBIG).await.unwrap();
+        let result = compare_images_normalized(&img1, &img2, 10, 10);
+        assert!(result < 5.0);
+    }
+
+    #[tokio::test]
+    async fn compare_images_normalized_is_near_zero_for_the_same_image() {
+        let img = download_image(URLSMALL).await.unwrap();
+        let result = compare_images_normalized(&img, &img, 10, 10);
+        assert!(result < 1.0);
+    }
+}
\ No newline at end of file